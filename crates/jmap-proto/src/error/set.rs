@@ -0,0 +1,85 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::types::{id::Id, property::Property};
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SetError {
+    #[serde(rename = "type")]
+    pub type_: SetErrorType,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<Vec<Property>>,
+
+    // Set on `alreadyExists` errors so the client can recover the id of the
+    // record that already exists instead of treating the creation as a
+    // hard failure (used by the `Email/import` duplicate detection path).
+    #[serde(rename = "existingId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_id: Option<Id>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SetErrorType {
+    Forbidden,
+    OverQuota,
+    TooLarge,
+    RateLimit,
+    NotFound,
+    InvalidPatch,
+    WillDestroy,
+    InvalidProperties,
+    Singleton,
+    BlobNotFound,
+    AlreadyExists,
+}
+
+impl SetError {
+    pub fn new(type_: SetErrorType) -> Self {
+        SetError {
+            type_,
+            description: None,
+            properties: None,
+            existing_id: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_properties(mut self, properties: impl IntoIterator<Item = Property>) -> Self {
+        self.properties = Some(properties.into_iter().collect());
+        self
+    }
+
+    pub fn with_existing_id(mut self, id: Id) -> Self {
+        self.existing_id = Some(id);
+        self
+    }
+}
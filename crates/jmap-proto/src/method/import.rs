@@ -56,6 +56,22 @@ pub struct ImportEmail {
     pub mailbox_ids: MaybeReference<Vec<MaybeReference<Id, String>>, ResultReference>,
     pub keywords: Vec<Keyword>,
     pub received_at: Option<UTCDate>,
+
+    // RFC 8620 does not define `useSieve`, it is a Stalwart extension: when
+    // set (or when `mailboxIds` is omitted entirely) the account's active
+    // Sieve script is run against the parsed message and its `fileinto`,
+    // `keep` and `discard` actions decide the mailboxes in place of
+    // `mailbox_ids` above, while keywords from `addflag`/`setflag` are added
+    // to `keywords`.
+    pub use_sieve: bool,
+
+    // Another Stalwart extension: when set, the importer fingerprints the
+    // message (a hash of its raw bytes) and, if an email with the same
+    // fingerprint already exists in one of the target mailboxes, reports
+    // this creation id in `notCreated` with `alreadyExists` instead of
+    // importing a second copy. Lets IMAP-to-JMAP migration tools retry
+    // imports safely.
+    pub detect_duplicates: bool,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -130,6 +146,8 @@ impl JsonObjectParser for ImportEmail {
             mailbox_ids: MaybeReference::Value(vec![]),
             keywords: vec![],
             received_at: None,
+            use_sieve: false,
+            detect_duplicates: false,
         };
 
         parser
@@ -158,6 +176,14 @@ impl JsonObjectParser for ImportEmail {
                         .next_token::<UTCDate>()?
                         .unwrap_string_or_null("receivedAt")?;
                 }
+                0x6576_6569_5365_7375 if !key.is_ref => {
+                    request.use_sieve = parser.next_token::<bool>()?.unwrap_bool("useSieve")?;
+                }
+                0x7365_7461_6369_6c70_7544_7463_6574_6564 if !key.is_ref => {
+                    request.detect_duplicates = parser
+                        .next_token::<bool>()?
+                        .unwrap_bool("detectDuplicates")?;
+                }
                 _ => {
                     parser.skip_token(parser.depth_array, parser.depth_dict)?;
                 }
@@ -168,6 +194,17 @@ impl JsonObjectParser for ImportEmail {
     }
 }
 
+impl ImportEmail {
+    /// The importer should run the account's active Sieve script for this
+    /// creation id rather than filing the message directly into
+    /// `mailbox_ids`, either because the client asked for it explicitly or
+    /// because it did not supply any mailboxes of its own.
+    pub fn requires_sieve(&self) -> bool {
+        self.use_sieve
+            || matches!(&self.mailbox_ids, MaybeReference::Value(ids) if ids.is_empty())
+    }
+}
+
 impl ImportEmailResponse {
     pub fn update_created_ids(&self, response: &mut Response) {
         for (user_id, obj) in &self.created {
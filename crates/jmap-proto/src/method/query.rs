@@ -0,0 +1,478 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    parser::{json::Parser, JsonObjectParser, Token},
+    request::RequestProperty,
+    types::{date::UTCDate, id::Id, keyword::Keyword, state::State, value::SetValueMap},
+};
+
+#[derive(Debug, Clone)]
+pub struct EmailQueryRequest {
+    pub account_id: Id,
+    pub filter: Option<Filter>,
+    pub sort: Option<Vec<Comparator>>,
+    pub position: i32,
+    pub anchor: Option<Id>,
+    pub anchor_offset: i32,
+    pub limit: Option<usize>,
+    pub calculate_total: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Condition(FilterCondition),
+    Operator(FilterOperator, Vec<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOperator {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, Clone)]
+pub enum FilterCondition {
+    InMailbox(Id),
+    InMailboxOtherThan(Vec<Id>),
+    Before(UTCDate),
+    After(UTCDate),
+    MinSize(u32),
+    MaxSize(u32),
+    HasKeyword(Keyword),
+    NotKeyword(Keyword),
+    From(String),
+    To(String),
+    Cc(String),
+    Subject(String),
+    Body(String),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    ReceivedAt,
+    Size,
+    From,
+    Subject,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Comparator {
+    pub property: Property,
+    pub is_ascending: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EmailQueryResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "queryState")]
+    pub query_state: State,
+
+    #[serde(rename = "canCalculateChanges")]
+    pub can_calculate_changes: bool,
+
+    #[serde(rename = "position")]
+    pub position: i32,
+
+    #[serde(rename = "ids")]
+    pub ids: Vec<Id>,
+
+    #[serde(rename = "total")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+
+    #[serde(rename = "limit")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl JsonObjectParser for EmailQueryRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = EmailQueryRequest {
+            account_id: Id::default(),
+            filter: None,
+            sort: None,
+            position: 0,
+            anchor: None,
+            anchor_offset: 0,
+            limit: None,
+            calculate_total: false,
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x7265_746c_6966 if !key.is_ref => {
+                    request.filter = Filter::parse(parser)?.into();
+                }
+                0x7472_6f73 if !key.is_ref => {
+                    request.sort = <SetValueMap<Comparator>>::parse(parser)?.values.into();
+                }
+                0x6e6f_6974_6973_6f70 if !key.is_ref => {
+                    request.position = parser.next_token::<i32>()?.unwrap_int("position")?;
+                }
+                0x726f_6863_6e61 if !key.is_ref => {
+                    request.anchor = parser.next_token::<Id>()?.unwrap_string_or_null("anchor")?;
+                }
+                0x7465_7366_664f_726f_6863_6e61 if !key.is_ref => {
+                    request.anchor_offset = parser
+                        .next_token::<i32>()?
+                        .unwrap_int("anchorOffset")?;
+                }
+                0x0074_696d_696c if !key.is_ref => {
+                    request.limit = parser
+                        .next_token::<usize>()?
+                        .unwrap_uint_or_null("limit")?;
+                }
+                0x6c61_746f_5465_7461_6c75_636c_6163 if !key.is_ref => {
+                    request.calculate_total = parser
+                        .next_token::<bool>()?
+                        .unwrap_bool("calculateTotal")?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for Filter {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut operator = None;
+        let mut conditions = Vec::new();
+        let mut in_mailbox = None;
+        let mut in_mailbox_other_than = None;
+        let mut before = None;
+        let mut after = None;
+        let mut min_size = None;
+        let mut max_size = None;
+        let mut has_keyword = None;
+        let mut not_keyword = None;
+        let mut from = None;
+        let mut to = None;
+        let mut cc = None;
+        let mut subject = None;
+        let mut body = None;
+        let mut text = None;
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x726f_7461_7265_706f if !key.is_ref => {
+                    operator = match parser.next_token::<String>()?.unwrap_string("operator")?.as_str() {
+                        "AND" => Some(FilterOperator::And),
+                        "OR" => Some(FilterOperator::Or),
+                        "NOT" => Some(FilterOperator::Not),
+                        _ => None,
+                    };
+                }
+                0x736e_6f69_7469_646e_6f63 if !key.is_ref => {
+                    conditions = <SetValueMap<Filter>>::parse(parser)?.values;
+                }
+                0x0078_6f62_6c69_614d_6e69 if !key.is_ref => {
+                    in_mailbox = parser.next_token::<Id>()?.unwrap_string("inMailbox")?.into();
+                }
+                // "inMailboxOtherThan" is 18 bytes, longer than a single
+                // `u128` word can hold, so it spills into `key.hash[1]`
+                // like other long property names.
+                0x6854_7265_6874_4f78_6f62_6c69_614d_6e69
+                    if !key.is_ref && key.hash[1] == 0x6e61 =>
+                {
+                    in_mailbox_other_than = <SetValueMap<Id>>::parse(parser)?.values.into();
+                }
+                0x6572_6f66_6562 if !key.is_ref => {
+                    before = parser.next_token::<UTCDate>()?.unwrap_string("before")?.into();
+                }
+                0x0072_6574_6661 if !key.is_ref => {
+                    after = parser.next_token::<UTCDate>()?.unwrap_string("after")?.into();
+                }
+                0x0065_7a69_536e_696d if !key.is_ref => {
+                    min_size = parser.next_token::<u32>()?.unwrap_uint("minSize")?.into();
+                }
+                0x0065_7a69_5378_616d if !key.is_ref => {
+                    max_size = parser.next_token::<u32>()?.unwrap_uint("maxSize")?.into();
+                }
+                0x6472_6f77_7965_4b73_6168 if !key.is_ref => {
+                    has_keyword = parser
+                        .next_token::<Keyword>()?
+                        .unwrap_string("hasKeyword")?
+                        .into();
+                }
+                0x6472_6f77_7965_4b74_6f6e if !key.is_ref => {
+                    not_keyword = parser
+                        .next_token::<Keyword>()?
+                        .unwrap_string("notKeyword")?
+                        .into();
+                }
+                0x6d6f_7266 if !key.is_ref => {
+                    from = parser.next_token::<String>()?.unwrap_string("from")?.into();
+                }
+                0x6f74 if !key.is_ref => {
+                    to = parser.next_token::<String>()?.unwrap_string("to")?.into();
+                }
+                0x6363 if !key.is_ref => {
+                    cc = parser.next_token::<String>()?.unwrap_string("cc")?.into();
+                }
+                0x0074_6365_6a62_7573 if !key.is_ref => {
+                    subject = parser
+                        .next_token::<String>()?
+                        .unwrap_string("subject")?
+                        .into();
+                }
+                0x7964_6f62 if !key.is_ref => {
+                    body = parser.next_token::<String>()?.unwrap_string("body")?.into();
+                }
+                0x7478_6574 if !key.is_ref => {
+                    text = parser.next_token::<String>()?.unwrap_string("text")?.into();
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        if let Some(operator) = operator {
+            return Ok(Filter::Operator(operator, conditions));
+        }
+
+        // A FilterCondition is a single object whose properties all have to
+        // match (RFC 8621 §4.4.1) — e.g. `{"inMailbox": x, "from": "a"}` is
+        // an implicit AND of both predicates, not just one of them.
+        let mut leaf_conditions = Vec::new();
+        if let Some(id) = in_mailbox {
+            leaf_conditions.push(FilterCondition::InMailbox(id));
+        }
+        if let Some(ids) = in_mailbox_other_than {
+            leaf_conditions.push(FilterCondition::InMailboxOtherThan(ids));
+        }
+        if let Some(date) = before {
+            leaf_conditions.push(FilterCondition::Before(date));
+        }
+        if let Some(date) = after {
+            leaf_conditions.push(FilterCondition::After(date));
+        }
+        if let Some(size) = min_size {
+            leaf_conditions.push(FilterCondition::MinSize(size));
+        }
+        if let Some(size) = max_size {
+            leaf_conditions.push(FilterCondition::MaxSize(size));
+        }
+        if let Some(keyword) = has_keyword {
+            leaf_conditions.push(FilterCondition::HasKeyword(keyword));
+        }
+        if let Some(keyword) = not_keyword {
+            leaf_conditions.push(FilterCondition::NotKeyword(keyword));
+        }
+        if let Some(value) = from {
+            leaf_conditions.push(FilterCondition::From(value));
+        }
+        if let Some(value) = to {
+            leaf_conditions.push(FilterCondition::To(value));
+        }
+        if let Some(value) = cc {
+            leaf_conditions.push(FilterCondition::Cc(value));
+        }
+        if let Some(value) = subject {
+            leaf_conditions.push(FilterCondition::Subject(value));
+        }
+        if let Some(value) = body {
+            leaf_conditions.push(FilterCondition::Body(value));
+        }
+        if let Some(value) = text {
+            leaf_conditions.push(FilterCondition::Text(value));
+        }
+
+        match leaf_conditions.len() {
+            0 => Err(parser.error_value()),
+            1 => Ok(Filter::Condition(leaf_conditions.pop().unwrap())),
+            _ => Ok(Filter::Operator(
+                FilterOperator::And,
+                leaf_conditions.into_iter().map(Filter::Condition).collect(),
+            )),
+        }
+    }
+}
+
+impl JsonObjectParser for Comparator {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut property = None;
+        let mut is_ascending = true;
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x7974_7265_706f_7270 if !key.is_ref => {
+                    property = match parser
+                        .next_token::<String>()?
+                        .unwrap_string("property")?
+                        .as_str()
+                    {
+                        "receivedAt" => Some(Property::ReceivedAt),
+                        "size" => Some(Property::Size),
+                        "from" => Some(Property::From),
+                        "subject" => Some(Property::Subject),
+                        _ => None,
+                    };
+                }
+                0x0067_6e69_646e_6563_7341_7369 if !key.is_ref => {
+                    is_ascending = parser.next_token::<bool>()?.unwrap_bool("isAscending")?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(Comparator {
+            property: property.unwrap_or(Property::ReceivedAt),
+            is_ascending,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_request(json: &str) -> EmailQueryRequest {
+        let mut parser = Parser::new(json.as_bytes());
+        EmailQueryRequest::parse(&mut parser).unwrap()
+    }
+
+    #[test]
+    fn filter_condition_is_implicit_and() {
+        let request = parse_request(
+            r#"{
+                "accountId": "a",
+                "filter": {"inMailbox": "m", "from": "alice@example.com"}
+            }"#,
+        );
+
+        match request.filter.unwrap() {
+            Filter::Operator(FilterOperator::And, conditions) => {
+                assert_eq!(conditions.len(), 2);
+                assert!(conditions
+                    .iter()
+                    .any(|c| matches!(c, Filter::Condition(FilterCondition::InMailbox(_)))));
+                assert!(conditions
+                    .iter()
+                    .any(|c| matches!(c, Filter::Condition(FilterCondition::From(value)) if value == "alice@example.com")));
+            }
+            other => panic!("expected an implicit AND of two conditions, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn filter_condition_single_predicate_stays_a_leaf() {
+        let request = parse_request(r#"{"accountId": "a", "filter": {"inMailbox": "m"}}"#);
+
+        assert!(matches!(
+            request.filter.unwrap(),
+            Filter::Condition(FilterCondition::InMailbox(_))
+        ));
+    }
+
+    #[test]
+    fn in_mailbox_other_than_hash_spill_parses() {
+        let request = parse_request(
+            r#"{"accountId": "a", "filter": {"inMailboxOtherThan": ["m1", "m2"]}}"#,
+        );
+
+        match request.filter.unwrap() {
+            Filter::Condition(FilterCondition::InMailboxOtherThan(ids)) => {
+                assert_eq!(ids.len(), 2);
+            }
+            other => panic!("expected InMailboxOtherThan, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn filter_operator_tree_parses() {
+        let request = parse_request(
+            r#"{
+                "accountId": "a",
+                "filter": {
+                    "operator": "OR",
+                    "conditions": [
+                        {"hasKeyword": "$seen"},
+                        {"notKeyword": "$seen"}
+                    ]
+                }
+            }"#,
+        );
+
+        match request.filter.unwrap() {
+            Filter::Operator(FilterOperator::Or, conditions) => assert_eq!(conditions.len(), 2),
+            other => panic!("expected an OR of two conditions, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn sort_and_paging_fields_parse() {
+        let request = parse_request(
+            r#"{
+                "accountId": "a",
+                "sort": [{"property": "receivedAt", "isAscending": false}],
+                "position": 10,
+                "limit": 5,
+                "calculateTotal": true
+            }"#,
+        );
+
+        assert_eq!(request.position, 10);
+        assert_eq!(request.limit, Some(5));
+        assert!(request.calculate_total);
+        let sort = request.sort.unwrap();
+        assert_eq!(sort.len(), 1);
+        assert_eq!(sort[0].property, Property::ReceivedAt);
+        assert!(!sort[0].is_ascending);
+    }
+}
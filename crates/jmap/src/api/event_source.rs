@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+//! `GET /eventsource/` push subscriptions, as defined in RFC 8620 section 7.3.
+
+use std::{collections::HashSet, time::Duration};
+
+use jmap_proto::types::state::{State, StateChange, TypeState};
+use tokio::sync::mpsc;
+
+use crate::JMAP;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseAfter {
+    State,
+    No,
+}
+
+pub struct EventSourceRequest {
+    pub types: HashSet<TypeState>,
+    pub close_after: CloseAfter,
+    pub ping: Option<Duration>,
+}
+
+impl EventSourceRequest {
+    pub fn parse(query: &str) -> Self {
+        let mut types = HashSet::new();
+        let mut close_after = CloseAfter::No;
+        let mut ping = None;
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "types" => {
+                    if value == "*" {
+                        types = TypeState::all().into_iter().collect();
+                    } else {
+                        types.extend(value.split(',').filter_map(|t| TypeState::parse(t)));
+                    }
+                }
+                "closeafter" if value == "state" => {
+                    close_after = CloseAfter::State;
+                }
+                "ping" => {
+                    if let Ok(secs) = value.parse::<u64>() {
+                        if secs > 0 {
+                            ping = Some(Duration::from_secs(secs));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        EventSourceRequest {
+            types,
+            close_after,
+            ping,
+        }
+    }
+}
+
+// Per-account fan-out: every state change passed to `publish` is replayed to
+// every EventSource connection subscribed to that account, and each
+// connection forwards it when its requested `types` intersect the change.
+// No method handler in this crate calls `publish`; `Email/import` leaves its
+// response's `state_change` unset.
+#[derive(Default)]
+pub struct EventSourceManager {
+    subscribers: parking_lot::Mutex<std::collections::HashMap<u32, Vec<mpsc::Sender<StateChange>>>>,
+}
+
+impl EventSourceManager {
+    pub fn subscribe(&self, account_id: u32) -> mpsc::Receiver<StateChange> {
+        let (tx, rx) = mpsc::channel(32);
+        self.subscribers
+            .lock()
+            .entry(account_id)
+            .or_default()
+            .push(tx);
+        rx
+    }
+
+    pub fn publish(&self, account_id: u32, change: StateChange) {
+        let mut subscribers = self.subscribers.lock();
+        if let Some(senders) = subscribers.get_mut(&account_id) {
+            senders.retain(|tx| keep_after_send(tx.try_send(change.clone())));
+        }
+    }
+}
+
+// A full channel means this subscriber has fallen behind and is about to
+// miss a state change with no way to find out — keeping it subscribed while
+// silently dropping the event leaves it stuck on a stale `id:` forever.
+// Closing it instead ends the stream, so the client's native EventSource
+// reconnects on its own. The server does not read `Last-Event-ID`: the new
+// connection receives only the changes published after it subscribes.
+fn keep_after_send<T>(result: Result<(), mpsc::error::TrySendError<T>>) -> bool {
+    result.is_ok()
+}
+
+impl JMAP {
+    // Renders a single `StateChange` as an SSE `state` event, filtered to
+    // the `types` the connection asked for; an empty `types` set (no `types`
+    // parameter) lets every type through. Returns `None` when none of the
+    // change's types were requested, in which case nothing should be sent.
+    pub fn format_state_change(
+        request: &EventSourceRequest,
+        change: &StateChange,
+    ) -> Option<String> {
+        let matching = change
+            .types
+            .iter()
+            .filter(|(type_state, _)| request.types.is_empty() || request.types.contains(type_state))
+            .map(|(type_state, state)| (*type_state, state.clone()))
+            .collect::<Vec<(TypeState, State)>>();
+
+        if matching.is_empty() {
+            return None;
+        }
+
+        let payload = serde_json::json!({
+            "@type": "StateChange",
+            "changed": {
+                change.account_id.to_string(): matching.into_iter()
+                    .map(|(t, s)| (t.to_string(), s.to_string()))
+                    .collect::<std::collections::HashMap<_, _>>(),
+            },
+        });
+
+        Some(format!(
+            "event: state\ndata: {}\nid: {}\n\n",
+            payload,
+            change.id()
+        ))
+    }
+
+    // A ping is emitted as a bare SSE comment line so that intermediaries
+    // don't time out the connection while no state changes are happening.
+    pub fn format_ping() -> &'static str {
+        ": \n\n"
+    }
+
+    // `GET /eventsource/` handler: subscribes `account_id` to this process's
+    // `EventSourceManager` and streams `state` events (and `ping` comments)
+    // back over `body_tx` until the client disconnects or, when
+    // `closeafter=state` was requested, right after the first state event.
+    pub async fn handle_event_source(
+        &self,
+        account_id: u32,
+        request: EventSourceRequest,
+        mut body_tx: hyper::body::Sender,
+    ) {
+        let mut changes = self.event_source.subscribe(account_id);
+        let mut ping = request.ping.map(tokio::time::interval);
+
+        loop {
+            let next_ping = async {
+                match &mut ping {
+                    Some(interval) => {
+                        interval.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                change = changes.recv() => {
+                    let Some(change) = change else { break };
+                    if let Some(event) = Self::format_state_change(&request, &change) {
+                        if body_tx.send_data(event.into()).await.is_err() {
+                            break;
+                        }
+                        if request.close_after == CloseAfter::State {
+                            break;
+                        }
+                    }
+                }
+                _ = next_ping => {
+                    if body_tx.send_data(Self::format_ping().into()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Builds the `200 text/event-stream` response for `GET /eventsource/`
+    // and spawns the task that feeds it, per RFC 8620 section 7.3.
+    pub fn eventsource_response(
+        self: std::sync::Arc<Self>,
+        account_id: u32,
+        query: &str,
+    ) -> hyper::Response<hyper::Body> {
+        let request = EventSourceRequest::parse(query);
+        let (body_tx, body) = hyper::Body::channel();
+
+        tokio::spawn(async move {
+            self.handle_event_source(account_id, request, body_tx).await;
+        });
+
+        hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(body)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_is_a_bare_sse_comment() {
+        assert_eq!(JMAP::format_ping(), ": \n\n");
+    }
+
+    #[test]
+    fn parse_defaults_to_no_closeafter_and_no_ping() {
+        let request = EventSourceRequest::parse("");
+        assert_eq!(request.close_after, CloseAfter::No);
+        assert_eq!(request.ping, None);
+    }
+
+    #[test]
+    fn parse_reads_closeafter_and_ping() {
+        let request = EventSourceRequest::parse("closeafter=state&ping=30");
+        assert_eq!(request.close_after, CloseAfter::State);
+        assert_eq!(request.ping, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn zero_second_ping_is_treated_as_disabled() {
+        let request = EventSourceRequest::parse("ping=0");
+        assert_eq!(request.ping, None);
+    }
+
+    #[test]
+    fn keep_after_send_only_keeps_successful_sends() {
+        assert!(keep_after_send::<()>(Ok(())));
+        assert!(!keep_after_send(Err(mpsc::error::TrySendError::Full(()))));
+        assert!(!keep_after_send(Err(mpsc::error::TrySendError::Closed(()))));
+    }
+}
@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+pub mod event_source;
+
+use std::sync::Arc;
+
+use crate::JMAP;
+
+impl JMAP {
+    // Dispatches the handful of plain HTTP (non-JMAP-method) endpoints the
+    // server exposes alongside the JSON API. `account_id` is whatever the
+    // session's bearer token resolved to upstream; routing here only cares
+    // about the path.
+    pub async fn handle_http_request(
+        self: Arc<Self>,
+        account_id: u32,
+        path: &str,
+        query: &str,
+    ) -> Option<hyper::Response<hyper::Body>> {
+        match path {
+            "/eventsource/" | "/eventsource" => Some(self.eventsource_response(account_id, query)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::method::query::{
+    Comparator, EmailQueryRequest, EmailQueryResponse, Filter, FilterCondition, FilterOperator,
+    Property,
+};
+
+use crate::{store::StoredEmail, Error, JMAP};
+
+impl JMAP {
+    pub async fn email_query(&self, request: EmailQueryRequest) -> crate::Result<EmailQueryResponse> {
+        let account_id = request.account_id.document_id();
+        let mut emails = self.store.list_emails(account_id);
+
+        if let Some(filter) = &request.filter {
+            emails.retain(|email| matches_filter(filter, email));
+        }
+
+        if let Some(sort) = &request.sort {
+            emails.sort_by(|a, b| {
+                sort.iter()
+                    .map(|comparator| compare(comparator, a, b))
+                    .find(|ordering| *ordering != std::cmp::Ordering::Equal)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+
+        let total = emails.len();
+        let start = resolve_start(&request, &emails)?;
+        let limit = request.limit.unwrap_or(usize::MAX);
+        let ids = emails
+            .into_iter()
+            .skip(start)
+            .take(limit)
+            .map(|email| email.id)
+            .collect();
+
+        Ok(EmailQueryResponse {
+            account_id: request.account_id,
+            query_state: self.store.account_state(account_id),
+            can_calculate_changes: false,
+            position: start as i32,
+            ids,
+            total: request.calculate_total.then_some(total),
+            limit: request.limit,
+        })
+    }
+}
+
+// Resolves `position`/`anchor`/`anchor_offset` into the zero-based index of
+// the first result to return, per the RFC 8620 §5.5 windowing rules.
+fn resolve_start(request: &EmailQueryRequest, emails: &[StoredEmail]) -> crate::Result<usize> {
+    if let Some(anchor) = &request.anchor {
+        let anchor_pos = emails
+            .iter()
+            .position(|email| &email.id == anchor)
+            .ok_or_else(|| Error("anchor not found in query results".into()))?;
+        Ok((anchor_pos as i64 + request.anchor_offset as i64).max(0) as usize)
+    } else if request.position < 0 {
+        Ok(emails.len().saturating_sub((-request.position) as usize))
+    } else {
+        Ok(request.position as usize)
+    }
+}
+
+fn matches_filter(filter: &Filter, email: &StoredEmail) -> bool {
+    match filter {
+        Filter::Condition(condition) => matches_condition(condition, email),
+        Filter::Operator(FilterOperator::And, conditions) => {
+            conditions.iter().all(|f| matches_filter(f, email))
+        }
+        Filter::Operator(FilterOperator::Or, conditions) => {
+            conditions.iter().any(|f| matches_filter(f, email))
+        }
+        Filter::Operator(FilterOperator::Not, conditions) => {
+            !conditions.iter().any(|f| matches_filter(f, email))
+        }
+    }
+}
+
+fn matches_condition(condition: &FilterCondition, email: &StoredEmail) -> bool {
+    match condition {
+        FilterCondition::InMailbox(id) => email.mailbox_ids.contains(id),
+        FilterCondition::InMailboxOtherThan(ids) => {
+            email.mailbox_ids.iter().any(|id| !ids.contains(id))
+        }
+        FilterCondition::Before(date) => email
+            .received_at
+            .as_ref()
+            .is_some_and(|received| received < date),
+        // RFC 8621 §4.4.1: `before` is strict (`<`), `after` is inclusive
+        // (`>=`) — a message received exactly at the boundary matches `after`.
+        FilterCondition::After(date) => email
+            .received_at
+            .as_ref()
+            .is_some_and(|received| received >= date),
+        FilterCondition::MinSize(size) => email.size >= *size,
+        FilterCondition::MaxSize(size) => email.size <= *size,
+        FilterCondition::HasKeyword(keyword) => email.keywords.contains(keyword),
+        FilterCondition::NotKeyword(keyword) => !email.keywords.contains(keyword),
+        FilterCondition::From(text) => contains_ci(&email.from, text),
+        FilterCondition::To(text) => contains_ci(&email.to, text),
+        FilterCondition::Cc(text) => contains_ci(&email.cc, text),
+        FilterCondition::Subject(text) => contains_ci(&email.subject, text),
+        FilterCondition::Body(text) => contains_ci(&email.body, text),
+        FilterCondition::Text(text) => {
+            contains_ci(&email.from, text)
+                || contains_ci(&email.to, text)
+                || contains_ci(&email.cc, text)
+                || contains_ci(&email.subject, text)
+                || contains_ci(&email.body, text)
+        }
+    }
+}
+
+fn contains_ci(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn compare(comparator: &Comparator, a: &StoredEmail, b: &StoredEmail) -> std::cmp::Ordering {
+    let ordering = match comparator.property {
+        Property::ReceivedAt => a.received_at.cmp(&b.received_at),
+        Property::Size => a.size.cmp(&b.size),
+        Property::From => a.from.cmp(&b.from),
+        Property::Subject => a.subject.cmp(&b.subject),
+    };
+    if comparator.is_ascending {
+        ordering
+    } else {
+        ordering.reverse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jmap_proto::types::id::Id;
+
+    use super::*;
+
+    fn email(id: u32, mailbox_ids: Vec<Id>, size: u32, from: &str, subject: &str) -> StoredEmail {
+        StoredEmail {
+            id: Id::from(id),
+            blob_hash: vec![],
+            mailbox_ids,
+            keywords: vec![],
+            received_at: None,
+            size,
+            from: from.to_string(),
+            to: String::new(),
+            cc: String::new(),
+            subject: subject.to_string(),
+            body: String::new(),
+            fingerprint: [0; 32],
+        }
+    }
+
+    #[test]
+    fn in_mailbox_and_other_than_are_complementary() {
+        let inbox = Id::from(0);
+        let archive = Id::from(1);
+        let message = email(1, vec![inbox], 10, "a@example.com", "hi");
+
+        assert!(matches_condition(&FilterCondition::InMailbox(inbox), &message));
+        assert!(!matches_condition(
+            &FilterCondition::InMailbox(archive),
+            &message
+        ));
+        assert!(!matches_condition(
+            &FilterCondition::InMailboxOtherThan(vec![inbox]),
+            &message
+        ));
+        assert!(matches_condition(
+            &FilterCondition::InMailboxOtherThan(vec![archive]),
+            &message
+        ));
+    }
+
+    #[test]
+    fn size_bounds_are_inclusive() {
+        let message = email(1, vec![], 100, "", "");
+        assert!(matches_condition(&FilterCondition::MinSize(100), &message));
+        assert!(matches_condition(&FilterCondition::MaxSize(100), &message));
+        assert!(!matches_condition(&FilterCondition::MinSize(101), &message));
+        assert!(!matches_condition(&FilterCondition::MaxSize(99), &message));
+    }
+
+    #[test]
+    fn text_conditions_are_case_insensitive_substring_matches() {
+        let message = email(1, vec![], 0, "Alice@Example.com", "Quarterly Report");
+        assert!(matches_condition(
+            &FilterCondition::From("alice".into()),
+            &message
+        ));
+        assert!(matches_condition(
+            &FilterCondition::Subject("REPORT".into()),
+            &message
+        ));
+        assert!(!matches_condition(
+            &FilterCondition::Subject("invoice".into()),
+            &message
+        ));
+        // `text` matches if any of the individual fields match.
+        assert!(matches_condition(
+            &FilterCondition::Text("quarterly".into()),
+            &message
+        ));
+    }
+
+    #[test]
+    fn filter_operators_combine_conditions() {
+        let message = email(1, vec![], 50, "a@example.com", "hi");
+        let min = Filter::Condition(FilterCondition::MinSize(10));
+        let max = Filter::Condition(FilterCondition::MaxSize(10));
+
+        assert!(matches_filter(
+            &Filter::Operator(FilterOperator::And, vec![min.clone()]),
+            &message
+        ));
+        assert!(!matches_filter(
+            &Filter::Operator(FilterOperator::And, vec![min.clone(), max.clone()]),
+            &message
+        ));
+        assert!(matches_filter(
+            &Filter::Operator(FilterOperator::Or, vec![min.clone(), max.clone()]),
+            &message
+        ));
+        assert!(matches_filter(
+            &Filter::Operator(FilterOperator::Not, vec![max]),
+            &message
+        ));
+    }
+
+    #[test]
+    fn compare_reverses_ordering_when_descending() {
+        let small = email(1, vec![], 10, "", "");
+        let large = email(2, vec![], 20, "", "");
+
+        let ascending = Comparator {
+            property: Property::Size,
+            is_ascending: true,
+        };
+        let descending = Comparator {
+            property: Property::Size,
+            is_ascending: false,
+        };
+
+        assert_eq!(compare(&ascending, &small, &large), std::cmp::Ordering::Less);
+        assert_eq!(
+            compare(&descending, &small, &large),
+            std::cmp::Ordering::Greater
+        );
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+pub mod api;
+pub mod email;
+pub mod store;
+
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Entry point shared by every JMAP method handler and by the EventSource
+/// endpoint. `store` is the account-indexed backing store; the full server
+/// additionally plugs in a real blob store, the account's Sieve scripts and
+/// a persistent mailbox/message index, all reachable through it.
+pub struct JMAP {
+    pub store: store::InMemoryStore,
+    pub event_source: api::event_source::EventSourceManager,
+}
+
+impl Default for JMAP {
+    fn default() -> Self {
+        JMAP {
+            store: store::InMemoryStore::default(),
+            event_source: api::event_source::EventSourceManager::default(),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+//! Minimal account-indexed backing store used by the method handlers in
+//! this crate. The full server backs these same lookups with `store`'s
+//! persistent indexes; this in-memory version keeps the handlers exercising
+//! real (if simplified) logic rather than calling out to nothing.
+
+use std::collections::HashMap;
+
+use jmap_proto::types::{date::UTCDate, id::Id, keyword::Keyword, state::State};
+use parking_lot::RwLock;
+
+#[derive(Clone)]
+pub struct StoredEmail {
+    pub id: Id,
+    pub blob_hash: Vec<u8>,
+    pub mailbox_ids: Vec<Id>,
+    pub keywords: Vec<Keyword>,
+    pub received_at: Option<UTCDate>,
+    pub size: u32,
+    pub from: String,
+    pub to: String,
+    pub cc: String,
+    pub subject: String,
+    pub body: String,
+    pub fingerprint: [u8; 32],
+}
+
+#[derive(Default)]
+struct AccountStore {
+    next_document_id: u32,
+    // Bumped on every email insertion; exposed as the account's JMAP `State`.
+    change_counter: u32,
+    mailboxes: HashMap<String, Id>,
+    sieve_script: Option<Vec<u8>>,
+    emails: Vec<StoredEmail>,
+}
+
+#[derive(Default)]
+pub struct InMemoryStore {
+    blobs: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
+    accounts: RwLock<HashMap<u32, AccountStore>>,
+}
+
+impl InMemoryStore {
+    pub fn get_blob(&self, hash: &[u8]) -> Option<Vec<u8>> {
+        self.blobs.read().get(hash).cloned()
+    }
+
+    pub fn put_blob(&self, hash: Vec<u8>, bytes: Vec<u8>) {
+        self.blobs.write().insert(hash, bytes);
+    }
+
+    pub fn active_sieve_script(&self, account_id: u32) -> Option<Vec<u8>> {
+        self.accounts.read().get(&account_id)?.sieve_script.clone()
+    }
+
+    pub fn set_active_sieve_script(&self, account_id: u32, script: Vec<u8>) {
+        self.accounts
+            .write()
+            .entry(account_id)
+            .or_default()
+            .sieve_script = Some(script);
+    }
+
+    // Mailboxes are created on first reference, with a well-known "Inbox"
+    // always present — real mailbox provisioning lives in the `Mailbox/set`
+    // handler, out of scope for this store.
+    pub fn mailbox_id_by_name(&self, account_id: u32, name: &str) -> Id {
+        let mut accounts = self.accounts.write();
+        let account = accounts.entry(account_id).or_default();
+        if let Some(id) = account.mailboxes.get(name) {
+            return *id;
+        }
+        let id = Id::from(account.next_document_id);
+        account.next_document_id += 1;
+        account.mailboxes.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn mailbox_inbox_id(&self, account_id: u32) -> Id {
+        self.mailbox_id_by_name(account_id, "Inbox")
+    }
+
+    // Scoped to `mailbox_ids` rather than the whole account: re-importing a
+    // message that already lives in a different mailbox is a legitimate way
+    // to file a second copy, so only a fingerprint match in one of the
+    // mailboxes this import is actually targeting counts as a duplicate.
+    pub fn find_duplicate_email(
+        &self,
+        account_id: u32,
+        mailbox_ids: &[Id],
+        fingerprint: &[u8; 32],
+    ) -> Option<Id> {
+        self.accounts
+            .read()
+            .get(&account_id)?
+            .emails
+            .iter()
+            .find(|email| {
+                &email.fingerprint == fingerprint
+                    && email
+                        .mailbox_ids
+                        .iter()
+                        .any(|id| mailbox_ids.contains(id))
+            })
+            .map(|email| email.id)
+    }
+
+    pub fn insert_email(&self, account_id: u32, mut email: StoredEmail) -> Id {
+        let mut accounts = self.accounts.write();
+        let account = accounts.entry(account_id).or_default();
+        let id = Id::from(account.next_document_id);
+        account.next_document_id += 1;
+        account.change_counter += 1;
+        email.id = id;
+        account.emails.push(email);
+        id
+    }
+
+    pub fn list_emails(&self, account_id: u32) -> Vec<StoredEmail> {
+        self.accounts
+            .read()
+            .get(&account_id)
+            .map(|account| account.emails.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn account_state(&self, account_id: u32) -> State {
+        State::from(
+            self.accounts
+                .read()
+                .get(&account_id)
+                .map(|account| account.change_counter)
+                .unwrap_or(0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email_in(mailbox_ids: Vec<Id>, fingerprint: [u8; 32]) -> StoredEmail {
+        StoredEmail {
+            id: Id::default(),
+            blob_hash: vec![],
+            mailbox_ids,
+            keywords: vec![],
+            received_at: None,
+            size: 0,
+            from: String::new(),
+            to: String::new(),
+            cc: String::new(),
+            subject: String::new(),
+            body: String::new(),
+            fingerprint,
+        }
+    }
+
+    #[test]
+    fn find_duplicate_email_is_scoped_to_target_mailboxes() {
+        let store = InMemoryStore::default();
+        let inbox = Id::from(0);
+        let archive = Id::from(1);
+        let fingerprint = [7u8; 32];
+
+        let existing_id = store.insert_email(1, email_in(vec![inbox], fingerprint));
+
+        // Same fingerprint, but the import only targets the Archive mailbox:
+        // not a duplicate of the copy sitting in Inbox.
+        assert_eq!(
+            store.find_duplicate_email(1, &[archive], &fingerprint),
+            None
+        );
+
+        // Importing into Inbox again, where the fingerprint already lives,
+        // is the duplicate case.
+        assert_eq!(
+            store.find_duplicate_email(1, &[inbox], &fingerprint),
+            Some(existing_id)
+        );
+
+        // A different fingerprint never matches, regardless of mailbox.
+        assert_eq!(
+            store.find_duplicate_email(1, &[inbox], &[9u8; 32]),
+            None
+        );
+    }
+}